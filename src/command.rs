@@ -0,0 +1,10 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerCommand {
+    Play,
+    Pause,
+    Resume,
+    Stop,
+    Seek(f64),
+    SetSpeed(f64),
+    Transpose(i32),
+}
@@ -9,7 +9,7 @@ pub fn convert_from_midi(file_name: String, midi: Midi) {
         let mut key = File::create(format!("{}.txt", file_name.to_string())).unwrap();
         let mut key_phone = File::create(format!("phone-{}.txt", file_name)).unwrap();
         let mut res = String::new();
-        for e in midi.events.lock().unwrap().iter() {
+        for e in midi.events.lock().unwrap().iter().filter(|e| e.on) {
             match e.press {
                 24 => res.push('z'),
                 26 => res.push('x'),
@@ -1,6 +1,8 @@
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::sleep;
 use std::time::Duration;
 
@@ -10,17 +12,25 @@ use portable_atomic::AtomicF64;
 use rayon::prelude::*;
 use rayon::ThreadPool;
 
+use crate::command::PlayerCommand;
 use crate::maps::{gen, vr};
+use crate::output::MidiOutput;
+use crate::playlist::{Advance, Playlist};
+use crate::preview::{PreviewSynth, PREVIEW};
 use crate::ui::Mode;
 
-pub static SPEED: AtomicF64 = AtomicF64::new(1.0);
-pub static IS_PLAY: AtomicBool = AtomicBool::new(false);
 pub static PLAYING: AtomicBool = AtomicBool::new(false);
-pub static PAUSE: AtomicBool = AtomicBool::new(false);
 
-pub static SPACE: AtomicBool = AtomicBool::new(false);
-pub static CTRL: AtomicBool = AtomicBool::new(false);
-pub static BACK: AtomicBool = AtomicBool::new(false);
+// Musical time, in milliseconds, unaffected by SPEED — POSITION and DURATION
+// are always directly comparable, at any playback speed.
+pub static POSITION: AtomicF64 = AtomicF64::new(0.0);
+pub static DURATION: AtomicF64 = AtomicF64::new(0.0);
+
+// Whole-song or A-B loop toggle, in milliseconds; LOOP_END negative means
+// "end of song".
+pub static LOOP_ENABLED: AtomicBool = AtomicBool::new(false);
+pub static LOOP_START: AtomicF64 = AtomicF64::new(0.0);
+pub static LOOP_END: AtomicF64 = AtomicF64::new(-1.0);
 
 static MAP: &'static [i32] = &[
     24, 26, 28, 29, 31, 33, 35, 36, 38, 40, 41, 43, 45, 47, 48, 50, 52, 53, 55, 57, 59, 60, 62, 64,
@@ -32,6 +42,10 @@ pub struct Midi {
     pub file_name: Arc<Mutex<Option<PathBuf>>>,
     pub events: Arc<Mutex<Vec<Event>>>,
     pub pool: Arc<ThreadPool>,
+    pub commands: Sender<PlayerCommand>,
+    receiver: Arc<Mutex<Receiver<PlayerCommand>>>,
+    shift: Arc<AtomicI32>,
+    pub playlist: Arc<Mutex<Playlist>>,
 }
 
 impl Midi {
@@ -41,129 +55,337 @@ impl Midi {
             .num_threads(2)
             .build()
             .unwrap();
+        let (commands, receiver) = mpsc::channel();
         Midi {
             file_name: Arc::new(Mutex::new(None)),
             events: Arc::new(Mutex::new(vec![])),
             pool: Arc::new(pool),
+            commands,
+            receiver: Arc::new(Mutex::new(receiver)),
+            shift: Arc::new(AtomicI32::new(0)),
+            playlist: Arc::new(Mutex::new(Playlist::new())),
         }
     }
 
+    pub fn send(&self, cmd: PlayerCommand) {
+        let _ = self.commands.send(cmd);
+    }
+
+    // Returns true if playback ran out of events on its own, false if it was
+    // cut short by a Stop command — only a natural finish advances the playlist.
     #[inline]
-    fn play<F: Fn(i32)>(&self, f: F) {
+    fn play<F: Fn(&Event)>(&self, f: F) -> bool {
         let events = self.events.lock().unwrap();
+        let total_time = events.iter().fold(0.0, |acc, e| acc + e.delay);
+        DURATION.store(total_time, Ordering::Relaxed);
+        POSITION.store(0.0, Ordering::Relaxed);
+
+        let receiver = self.receiver.lock().unwrap();
+        let mut speed = 1.0;
         let mut start_time = Local::now().timestamp_millis();
         let mut input_time = 0.0;
-        for e in events.iter(){
-            if PAUSE.load(Ordering::Relaxed) {
-                loop {
-                    if !PAUSE.load(Ordering::Relaxed) {
-                        input_time = e.delay;
-                        start_time = Local::now().timestamp_millis();
-                        break;
+        let mut index = 0;
+
+        while index < events.len() {
+            match receiver.try_recv() {
+                Ok(PlayerCommand::Stop) => return false,
+                Ok(PlayerCommand::SetSpeed(s)) => speed = s,
+                Ok(PlayerCommand::Transpose(n)) => {
+                    self.shift.fetch_add(n, Ordering::Relaxed);
+                }
+                Ok(PlayerCommand::Seek(ms)) => {
+                    seek_to(&events, ms, speed, &mut index, &mut input_time, &mut start_time);
+                    continue;
+                }
+                Ok(PlayerCommand::Pause) => {
+                    // Block until a command ends the pause instead of busy-polling.
+                    loop {
+                        match receiver.recv() {
+                            Ok(PlayerCommand::Resume) => {
+                                let position = POSITION.load(Ordering::Relaxed);
+                                start_time =
+                                    Local::now().timestamp_millis() - (position / speed) as i64;
+                                break;
+                            }
+                            Ok(PlayerCommand::Seek(ms)) => {
+                                seek_to(&events, ms, speed, &mut index, &mut input_time, &mut start_time);
+                            }
+                            Ok(PlayerCommand::SetSpeed(s)) => speed = s,
+                            Ok(PlayerCommand::Transpose(n)) => {
+                                self.shift.fetch_add(n, Ordering::Relaxed);
+                            }
+                            Ok(PlayerCommand::Stop) | Err(_) => return false,
+                            _ => {}
+                        }
                     }
                 }
+                Ok(PlayerCommand::Play) | Err(_) => {}
             }
-            input_time += e.delay / SPEED.load(Ordering::Relaxed);
+
+            let e = &events[index];
+            input_time += e.delay;
+            let wall_target = input_time / speed;
             let playback_time = (Local::now().timestamp_millis() - start_time) as f64;
-            let current_time = (input_time - playback_time) as u64;
+            let current_time = (wall_target - playback_time) as u64;
             if current_time > 0 {
                 sleep(Duration::from_millis(current_time));
             }
-            match IS_PLAY.load(Ordering::Relaxed) {
-                true => f(e.press),
-                false => break,
+            POSITION.store(input_time, Ordering::Relaxed);
+            f(e);
+            index += 1;
+
+            if LOOP_ENABLED.load(Ordering::Relaxed) {
+                let loop_end = LOOP_END.load(Ordering::Relaxed);
+                let loop_end = if loop_end >= 0.0 { loop_end } else { total_time };
+                if input_time >= loop_end || index >= events.len() {
+                    let loop_start = LOOP_START.load(Ordering::Relaxed).max(0.0);
+                    seek_to(&events, loop_start, speed, &mut index, &mut input_time, &mut start_time);
+                }
             }
         }
+        true
+    }
+
+    pub fn seek(&self, ms: f64) {
+        self.send(PlayerCommand::Seek(ms.max(0.0)));
+    }
+
+    pub fn set_loop(&self, enabled: bool, start: f64, end: Option<f64>) {
+        let start = start.max(0.0);
+        // An inverted or empty range would make play() rewind after every
+        // event, replaying the note at the loop start over and over, so fall
+        // back to looping the whole song instead.
+        let end = match end {
+            Some(end) if end > start => Some(end),
+            _ => None,
+        };
+        LOOP_ENABLED.store(enabled, Ordering::Relaxed);
+        LOOP_START.store(start, Ordering::Relaxed);
+        LOOP_END.store(end.unwrap_or(-1.0), Ordering::Relaxed);
+    }
+
+    pub fn stop(&self) {
+        self.send(PlayerCommand::Stop);
+        POSITION.store(0.0, Ordering::Relaxed);
     }
 
     pub fn init(&self) {
         let mid = self.clone();
         self.pool.spawn(move || {
-            if let Some(path) = rfd::FileDialog::new()
+            if let Some(paths) = rfd::FileDialog::new()
                 .add_filter("MIDI File", &["mid"])
-                .pick_file()
+                .pick_files()
             {
-                *mid.file_name.lock().unwrap() = Some(path.clone());
-
-                let file = std::fs::read(path).unwrap();
-                let smf = Smf::parse(&file).unwrap();
-                let fps = match smf.header.timing {
-                    Timing::Metrical(fps) => fps.as_int() as f64,
-                    _ => 480.0,
-                };
-
-                let mut raw_events = smf
-                    .tracks
-                    .into_iter()
-                    .map(|track| {
-                        let mut tick = 0.0;
-                        track
-                            .into_iter()
-                            .map(|event| {
-                                tick += event.delta.as_int() as f64;
-                                RawEvent {
-                                    event: event.kind,
-                                    tick,
-                                }
-                            })
-                            .collect::<Vec<_>>()
-                    })
-                    .flatten()
-                    .collect::<Vec<_>>();
-
-                raw_events.par_sort_by_key(|e| e.tick as u64);
-
-                let mut tick = 0.0;
-                let mut tempo = 500000.0;
-                *mid.events.lock().unwrap() = raw_events
-                    .into_iter()
-                    .filter_map(|event| match event.event {
-                        TrackEventKind::Meta(MetaMessage::Tempo(t)) => {
-                            tempo = t.as_int() as f64;
-                            None
-                        }
-                        TrackEventKind::Midi {
-                            message: MidiMessage::NoteOn { key, vel },
-                            ..
-                        } => {
-                            if vel > 0 {
-                                let time = (event.tick - tick) * (tempo / 1000.0 / fps);
-                                tick = event.tick;
-                                return Some(Event {
-                                    press: key.as_int() as i32,
-                                    delay: time,
-                                });
-                            }
-                            None
-                        }
-                        _ => None,
-                    })
-                    .collect::<Vec<_>>();
+                mid.playlist.lock().unwrap().extend(paths);
+                mid.load_current();
+            }
+        });
+    }
+
+    fn load_current(&self) {
+        let Some(path) = self.playlist.lock().unwrap().current().cloned() else {
+            return;
+        };
+        *self.file_name.lock().unwrap() = Some(path.clone());
+        *self.events.lock().unwrap() = parse_midi(&path);
+    }
+
+    // Stops the current track before swapping in the next/previous one, and
+    // runs on the pool so the caller doesn't block on the events lock the
+    // outgoing play() call is still holding.
+    fn skip_to(&self, tuned: bool, mode: Mode, advance: fn(&mut Playlist) -> Option<&PathBuf>) {
+        let mid = self.clone();
+        self.pool.spawn(move || {
+            mid.send(PlayerCommand::Stop);
+            let mut playlist = mid.playlist.lock().unwrap();
+            let advanced = advance(&mut playlist).is_some();
+            drop(playlist);
+            if advanced {
+                mid.load_current();
+                mid.playback(tuned, mode);
             }
         });
     }
 
+    pub fn next_track(&self, tuned: bool, mode: Mode) {
+        self.skip_to(tuned, mode, Playlist::next);
+    }
+
+    pub fn prev_track(&self, tuned: bool, mode: Mode) {
+        self.skip_to(tuned, mode, Playlist::prev);
+    }
+
     pub fn playback(&self, tuned: bool, mode: Mode) {
         let mid = self.clone();
         self.pool.spawn(move || {
             PLAYING.store(true, Ordering::Relaxed);
-            let mut shift = 0;
             if tuned {
-                shift = tune(mid.events.clone());
+                let shift = tune(mid.events.clone());
+                mid.shift.store(shift, Ordering::Relaxed);
+            } else {
+                // A manual Transpose during a previous playback shouldn't leak
+                // into an untuned one started fresh.
+                mid.shift.store(0, Ordering::Relaxed);
             }
-            let send = match mode {
-                Mode::GenShin => gen,
-                Mode::VRChat => vr,
+            let preview = if PREVIEW.load(Ordering::Relaxed) {
+                PreviewSynth::open()
+            } else {
+                None
+            };
+            let finished = match mode {
+                Mode::GenShin => mid.play(|e| {
+                    let key = e.press + mid.shift.load(Ordering::Relaxed);
+                    preview_note(&preview, key, e.on);
+                    if e.on {
+                        gen(key);
+                    }
+                }),
+                Mode::VRChat => mid.play(|e| {
+                    let key = e.press + mid.shift.load(Ordering::Relaxed);
+                    preview_note(&preview, key, e.on);
+                    if e.on {
+                        vr(key);
+                    }
+                }),
+                Mode::Midi(channel) => {
+                    let output = RefCell::new(MidiOutput::open());
+                    mid.play(|e| {
+                        let key = e.press + mid.shift.load(Ordering::Relaxed);
+                        preview_note(&preview, key, e.on);
+                        let mut output = output.borrow_mut();
+                        let Some(output) = output.as_mut() else {
+                            return;
+                        };
+                        if e.on {
+                            output.note_on(channel, key, e.velocity);
+                        } else {
+                            output.note_off(channel, key);
+                        }
+                    })
+                }
             };
-            mid.play(|key| {
-                send(key + shift);
-            });
             PLAYING.store(false, Ordering::Relaxed);
-            IS_PLAY.store(false, Ordering::Relaxed);
+
+            if finished {
+                match mid.playlist.lock().unwrap().advance_on_finish() {
+                    Advance::Repeat => mid.playback(tuned, mode),
+                    Advance::Track(path) => {
+                        *mid.file_name.lock().unwrap() = Some(path.clone());
+                        *mid.events.lock().unwrap() = parse_midi(&path);
+                        mid.playback(tuned, mode);
+                    }
+                    Advance::Stop => {}
+                }
+            }
         });
     }
 }
 
+// No-op when PREVIEW is off, so auditioning a file costs nothing unless asked.
+fn preview_note(preview: &Option<PreviewSynth>, key: i32, on: bool) {
+    let Some(preview) = preview else {
+        return;
+    };
+    if on {
+        preview.note_on(key);
+    } else {
+        preview.note_off(key);
+    }
+}
+
+fn parse_midi(path: &Path) -> Vec<Event> {
+    let file = std::fs::read(path).unwrap();
+    let smf = Smf::parse(&file).unwrap();
+    let fps = match smf.header.timing {
+        Timing::Metrical(fps) => fps.as_int() as f64,
+        _ => 480.0,
+    };
+
+    let mut raw_events = smf
+        .tracks
+        .into_iter()
+        .map(|track| {
+            let mut tick = 0.0;
+            track
+                .into_iter()
+                .map(|event| {
+                    tick += event.delta.as_int() as f64;
+                    RawEvent {
+                        event: event.kind,
+                        tick,
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+
+    raw_events.par_sort_by_key(|e| e.tick as u64);
+
+    let mut tick = 0.0;
+    let mut tempo = 500000.0;
+    raw_events
+        .into_iter()
+        .filter_map(|event| match event.event {
+            TrackEventKind::Meta(MetaMessage::Tempo(t)) => {
+                tempo = t.as_int() as f64;
+                None
+            }
+            TrackEventKind::Midi {
+                message: MidiMessage::NoteOn { key, vel },
+                ..
+            } => {
+                let time = (event.tick - tick) * (tempo / 1000.0 / fps);
+                tick = event.tick;
+                Some(Event {
+                    press: key.as_int() as i32,
+                    delay: time,
+                    on: vel > 0,
+                    velocity: vel.as_int(),
+                })
+            }
+            TrackEventKind::Midi {
+                message: MidiMessage::NoteOff { key, vel },
+                ..
+            } => {
+                let time = (event.tick - tick) * (tempo / 1000.0 / fps);
+                tick = event.tick;
+                Some(Event {
+                    press: key.as_int() as i32,
+                    delay: time,
+                    on: false,
+                    velocity: vel.as_int(),
+                })
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+}
+
+fn seek_to(
+    events: &[Event],
+    ms: f64,
+    speed: f64,
+    index: &mut usize,
+    input_time: &mut f64,
+    start_time: &mut i64,
+) {
+    let mut acc = 0.0;
+    *index = events.len().saturating_sub(1);
+    for (i, e) in events.iter().enumerate() {
+        acc += e.delay;
+        if acc >= ms {
+            *index = i;
+            break;
+        }
+    }
+    // Musical time just before the cursor event, so play() adding its delay
+    // lands on that event's own time rather than past it.
+    *input_time = events[..*index].iter().map(|e| e.delay).sum();
+    *start_time = Local::now().timestamp_millis() - (ms / speed) as i64;
+    POSITION.store(ms, Ordering::Relaxed);
+}
+
 struct RawEvent<'a> {
     event: TrackEventKind<'a>,
     tick: f64,
@@ -173,10 +395,13 @@ struct RawEvent<'a> {
 pub struct Event {
     pub press: i32,
     pub delay: f64,
+    // true for a note-on, false for a note-off (or a zero-velocity note-on).
+    pub on: bool,
+    pub velocity: u8,
 }
 
 fn tune(events: Arc<Mutex<Vec<Event>>>) -> i32 {
-    let len = events.lock().unwrap().len() as f32;
+    let len = events.lock().unwrap().iter().filter(|e| e.on).count() as f32;
     let mut up_hit = vec![];
     let mut down_hit = vec![];
     let mut up_max = 0.0;
@@ -219,7 +444,7 @@ fn tune_offset(
     direction: bool,
 ) {
     let mut hit_count = 0.0;
-    for msg in events.lock().unwrap().iter() {
+    for msg in events.lock().unwrap().iter().filter(|e| e.on) {
         let key = msg.press + offset;
         if MAP.contains(&key) {
             hit_count += 1.0;
@@ -0,0 +1,36 @@
+use midir::MidiOutput as MidirOutput;
+use midir::MidiOutputConnection;
+#[cfg(unix)]
+use midir::os::unix::VirtualOutput;
+
+pub struct MidiOutput {
+    conn: MidiOutputConnection,
+}
+
+impl MidiOutput {
+    // On Unix, falls back to a virtual port so the tool still works with
+    // nothing physically plugged in; elsewhere returns None when no port exists.
+    pub fn open() -> Option<Self> {
+        let output = MidirOutput::new("rs-genshin-midi-player").ok()?;
+        let conn = match output.ports().first() {
+            Some(port) => output.connect(port, "rs-genshin-midi-player-out").ok()?,
+            #[cfg(unix)]
+            None => output
+                .create_virtual("rs-genshin-midi-player")
+                .ok()?,
+            #[cfg(not(unix))]
+            None => return None,
+        };
+        Some(Self { conn })
+    }
+
+    pub fn note_on(&mut self, channel: u8, key: i32, velocity: u8) {
+        let status = 0x90 | (channel & 0x0F);
+        let _ = self.conn.send(&[status, key.clamp(0, 127) as u8, velocity]);
+    }
+
+    pub fn note_off(&mut self, channel: u8, key: i32) {
+        let status = 0x80 | (channel & 0x0F);
+        let _ = self.conn.send(&[status, key.clamp(0, 127) as u8, 0]);
+    }
+}
@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    One,
+    All,
+}
+
+pub enum Advance {
+    Repeat,
+    Track(PathBuf),
+    Stop,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Playlist {
+    pub tracks: Vec<PathBuf>,
+    pub order: Vec<usize>,
+    pub position: usize,
+    pub repeat: RepeatMode,
+    pub shuffle: bool,
+}
+
+impl Playlist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn extend(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.tracks.extend(paths);
+        self.reset_order();
+    }
+
+    pub fn current(&self) -> Option<&PathBuf> {
+        self.order.get(self.position).and_then(|&i| self.tracks.get(i))
+    }
+
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+        self.reset_order();
+    }
+
+    fn reset_order(&mut self) {
+        self.order = (0..self.tracks.len()).collect();
+        if self.shuffle {
+            self.order.shuffle(&mut thread_rng());
+        }
+        self.position = self.position.min(self.order.len().saturating_sub(1));
+    }
+
+    pub fn next(&mut self) -> Option<&PathBuf> {
+        if self.order.is_empty() {
+            return None;
+        }
+        self.position += 1;
+        if self.position >= self.order.len() {
+            self.position = 0;
+            if self.shuffle {
+                self.reset_order();
+            }
+        }
+        self.current()
+    }
+
+    pub fn prev(&mut self) -> Option<&PathBuf> {
+        if self.order.is_empty() {
+            return None;
+        }
+        self.position = if self.position == 0 {
+            self.order.len() - 1
+        } else {
+            self.position - 1
+        };
+        self.current()
+    }
+
+    pub fn advance_on_finish(&mut self) -> Advance {
+        match self.repeat {
+            RepeatMode::One => Advance::Repeat,
+            RepeatMode::All => self
+                .next()
+                .cloned()
+                .map(Advance::Track)
+                .unwrap_or(Advance::Stop),
+            RepeatMode::Off => {
+                if self.position + 1 < self.order.len() {
+                    self.next()
+                        .cloned()
+                        .map(Advance::Track)
+                        .unwrap_or(Advance::Stop)
+                } else {
+                    Advance::Stop
+                }
+            }
+        }
+    }
+}
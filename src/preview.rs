@@ -0,0 +1,101 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{SampleFormat, Stream};
+
+// Off by default so playback stays silent until the user opts in to audition.
+pub static PREVIEW: AtomicBool = AtomicBool::new(false);
+
+const MAX_VOICES: usize = 32;
+const ATTACK_SAMPLES: f32 = 200.0;
+const RELEASE_SAMPLES: f32 = 800.0;
+
+#[derive(Clone, Copy)]
+struct Voice {
+    key: i32,
+    phase: f32,
+    freq: f32,
+    gain: f32,
+    released: bool,
+}
+
+pub struct PreviewSynth {
+    voices: Arc<Mutex<Vec<Voice>>>,
+    _stream: Stream,
+}
+
+impl PreviewSynth {
+    pub fn open() -> Option<Self> {
+        let host = cpal::default_host();
+        let device = host.default_output_device()?;
+        let config = device.default_output_config().ok()?;
+        if config.sample_format() != SampleFormat::F32 {
+            return None;
+        }
+        let sample_rate = config.sample_rate().0 as f32;
+        let channels = config.channels() as usize;
+
+        let voices = Arc::new(Mutex::new(Vec::with_capacity(MAX_VOICES)));
+        let voices_cb = voices.clone();
+        let stream = device
+            .build_output_stream(
+                &config.into(),
+                move |data: &mut [f32], _| render(data, channels, sample_rate, &voices_cb),
+                |err| eprintln!("preview synth stream error: {err}"),
+                None,
+            )
+            .ok()?;
+        stream.play().ok()?;
+
+        Some(Self {
+            voices,
+            _stream: stream,
+        })
+    }
+
+    pub fn note_on(&self, key: i32) {
+        let mut voices = self.voices.lock().unwrap();
+        if voices.len() >= MAX_VOICES {
+            voices.remove(0);
+        }
+        let freq = 440.0 * 2f32.powf((key as f32 - 69.0) / 12.0);
+        voices.push(Voice {
+            key,
+            phase: 0.0,
+            freq,
+            gain: 0.0,
+            released: false,
+        });
+    }
+
+    pub fn note_off(&self, key: i32) {
+        for voice in self.voices.lock().unwrap().iter_mut() {
+            if voice.key == key && !voice.released {
+                voice.released = true;
+            }
+        }
+    }
+}
+
+// Linear attack/release envelope so notes don't click in or out.
+fn render(data: &mut [f32], channels: usize, sample_rate: f32, voices: &Arc<Mutex<Vec<Voice>>>) {
+    let mut voices = voices.lock().unwrap();
+    for frame in data.chunks_mut(channels) {
+        let mut sample = 0.0;
+        voices.retain_mut(|voice| {
+            voice.phase = (voice.phase + voice.freq / sample_rate) % 1.0;
+            let triangle = 4.0 * (voice.phase - 0.5).abs() - 1.0;
+            if voice.released {
+                voice.gain -= 1.0 / RELEASE_SAMPLES;
+            } else if voice.gain < 1.0 {
+                voice.gain += 1.0 / ATTACK_SAMPLES;
+            }
+            sample += triangle * voice.gain.clamp(0.0, 1.0) * 0.15;
+            voice.gain > 0.0
+        });
+        for out in frame.iter_mut() {
+            *out = sample;
+        }
+    }
+}